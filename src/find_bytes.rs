@@ -0,0 +1,160 @@
+use std::slice::from_raw_parts;
+
+use onig_sys;
+
+use super::Regex;
+
+impl Regex {
+    /// Searches `haystack` for the leftmost match, without requiring it
+    /// (or the regex's group names) to be valid UTF-8.
+    ///
+    /// Returns `None` if there's no match. See [`captures_bytes`] for the
+    /// full set of captured groups.
+    ///
+    /// [`captures_bytes`]: #method.captures_bytes
+    pub fn search_bytes<'t>(&'t self, haystack: &'t [u8]) -> Option<MatchBytes<'t>> {
+        self.captures_bytes(haystack).map(|caps| {
+            let (start, end) = caps.pos(0).unwrap();
+            MatchBytes { haystack: haystack, start: start, end: end }
+        })
+    }
+
+    /// Searches `haystack` for the leftmost match and returns its
+    /// captured groups, without requiring the haystack (or the regex's
+    /// group names) to be valid UTF-8.
+    ///
+    /// Returns `None` if there's no match.
+    pub fn captures_bytes<'t>(&'t self, haystack: &'t [u8]) -> Option<CapturesBytes<'t>> {
+        unsafe {
+            let region = onig_sys::onig_region_new();
+            let start = haystack.as_ptr();
+            let range = start.offset(haystack.len() as isize);
+            let result = onig_sys::onig_search(
+                self.raw,
+                start,
+                range,
+                start,
+                range,
+                region,
+                onig_sys::ONIG_OPTION_NONE
+            );
+            if result < 0 {
+                onig_sys::onig_region_free(region, 1);
+                return None;
+            }
+            let num_regs = (*region).num_regs as usize;
+            let beg = from_raw_parts((*region).beg, num_regs);
+            let end = from_raw_parts((*region).end, num_regs);
+            let spans = (0..num_regs).map(|i| {
+                if beg[i] < 0 { None } else { Some((beg[i] as usize, end[i] as usize)) }
+            }).collect();
+            onig_sys::onig_region_free(region, 1);
+            Some(CapturesBytes { regex: self, haystack: haystack, spans: spans })
+        }
+    }
+}
+
+/// A single match against a `&[u8]` haystack, as returned by
+/// [`Regex::search_bytes`].
+///
+/// Unlike `Match`, the span isn't required to fall on a UTF-8 boundary:
+/// Oniguruma can be compiled for encodings where matched byte ranges
+/// don't correspond to valid UTF-8 at all.
+///
+/// [`Regex::search_bytes`]: struct.Regex.html#method.search_bytes
+#[derive(Debug, Copy, Clone)]
+pub struct MatchBytes<'t> {
+    haystack: &'t [u8],
+    start: usize,
+    end: usize
+}
+
+impl<'t> MatchBytes<'t> {
+    /// Returns the byte offset of the start of the match.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the byte offset of the end of the match.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Returns the raw bytes the match covers, as a slice over the
+    /// original haystack.
+    pub fn as_bytes(&self) -> &'t [u8] {
+        &self.haystack[self.start..self.end]
+    }
+}
+
+/// The captures from matching a `Regex` against a `&[u8]` haystack.
+///
+/// Mirrors `Captures`, but group spans and names are raw bytes rather
+/// than `&str`, for haystacks (or Oniguruma encodings) that aren't
+/// UTF-8.
+pub struct CapturesBytes<'t> {
+    regex: &'t Regex,
+    haystack: &'t [u8],
+    spans: Vec<Option<(usize, usize)>>
+}
+
+impl<'t> CapturesBytes<'t> {
+    /// Returns the start/end byte offsets of group `pos`, or `None` if
+    /// the group didn't participate in the match.
+    pub fn pos(&self, pos: usize) -> Option<(usize, usize)> {
+        self.spans.get(pos).cloned().unwrap_or(None)
+    }
+
+    /// Returns the raw bytes matched by group `pos`, as a slice over the
+    /// original haystack.
+    pub fn at(&self, pos: usize) -> Option<&'t [u8]> {
+        self.pos(pos).map(|(start, end)| &self.haystack[start..end])
+    }
+
+    /// Returns the raw bytes matched by the named group `name`.
+    ///
+    /// A name can refer to more than one group (e.g. `(?<bar>...)(?<bar>...)`);
+    /// this returns the first one, in declaration order, that
+    /// participated in the match. See [`Regex::named_group_bytes`].
+    ///
+    /// [`Regex::named_group_bytes`]: struct.Regex.html#method.named_group_bytes
+    pub fn name(&self, name: &[u8]) -> Option<&'t [u8]> {
+        self.regex.named_group_bytes(name)?
+            .iter()
+            .filter_map(|&i| self.at(i as usize))
+            .next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    #[test]
+    fn test_search_bytes() {
+        let regex = Regex::new("l+").unwrap();
+        let m = regex.search_bytes(b"hello").unwrap();
+        assert_eq!(m.start(), 2);
+        assert_eq!(m.end(), 4);
+        assert_eq!(m.as_bytes(), b"ll");
+        assert!(regex.search_bytes(b"goodbye").is_none());
+    }
+
+    #[test]
+    fn test_captures_bytes_numbered_and_named() {
+        let regex = Regex::new("(?<foo>\\w+) (?<bar>\\w+)").unwrap();
+        let caps = regex.captures_bytes(b"hello world").unwrap();
+        assert_eq!(caps.at(0), Some(&b"hello world"[..]));
+        assert_eq!(caps.at(1), Some(&b"hello"[..]));
+        assert_eq!(caps.name(b"bar"), Some(&b"world"[..]));
+        assert_eq!(caps.name(b"unknown"), None);
+    }
+
+    #[test]
+    fn test_captures_bytes_non_utf8_haystack() {
+        let regex = Regex::new("\\xff(?<rest>.+)").unwrap();
+        let haystack = b"\xff\xfe\xfd";
+        let caps = regex.captures_bytes(haystack).unwrap();
+        assert_eq!(caps.name(b"rest"), Some(&b"\xfe\xfd"[..]));
+    }
+}
@@ -0,0 +1,31 @@
+extern crate libc;
+extern crate onig_sys;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+mod find_bytes;
+mod names;
+mod replace;
+
+pub use find_bytes::{CapturesBytes, MatchBytes};
+pub use names::{Names, NamesBytes};
+pub use replace::Replacer;
+
+/// A compiled Oniguruma regular expression.
+///
+/// `Regex` holds a uniquely-owned Oniguruma pattern and isn't `Clone`;
+/// share a compiled `Regex` across call sites with `Arc<Regex>`. It
+/// holds a raw Oniguruma pointer and isn't `Send` or `Sync`, so that
+/// `Arc` stays on one thread. The name-table caches below are reached through `&self`,
+/// so every holder of that `Arc` sees (and reuses) the same cached data
+/// without re-walking Oniguruma's name table.
+pub struct Regex {
+    raw: onig_sys::OnigRegex,
+    /// Cache for `named_group`, keyed by `&str` group name.
+    name_table_cache: Mutex<Option<Arc<HashMap<String, Vec<i32>>>>>,
+    /// Cache for `named_group_bytes`, keyed by raw group-name bytes.
+    name_table_cache_bytes: Mutex<Option<Arc<HashMap<Vec<u8>, Vec<i32>>>>>,
+    /// Cache backing `names`/`names_bytes`, in name-table order.
+    names_table_cache: Mutex<Option<Arc<Vec<(Box<[u8]>, Box<[i32]>)>>>>,
+}
@@ -1,9 +1,10 @@
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::iter::Iterator;
-use std::ptr::null;
 use std::str::from_utf8_unchecked;
 use std::slice::from_raw_parts;
-use libc::{c_int, c_uint, c_ulong, c_void, c_uchar};
+use std::sync::Arc;
+use libc::{c_int, c_void, c_uchar};
 
 use onig_sys;
 
@@ -15,45 +16,202 @@ impl Regex {
         unsafe { onig_sys::onig_number_of_names(self.raw) as usize }
     }
 
+    /// Returns the group indexes referred to by the named group `name`, or
+    /// `None` if the regex has no group with that name.
+    ///
+    /// The name-to-group map is built once per `Regex`, lazily on first
+    /// access, and cached behind an `Arc` on `self.name_table_cache` so
+    /// repeated lookups (including those made through every holder of an
+    /// `Arc<Regex>`, and through `Captures` derived from it) share it
+    /// rather than re-walking Oniguruma's name table. `Regex` isn't
+    /// `Clone`, so there are no clones of it to share the map with.
+    pub fn named_group<'r>(&'r self, name: &str) -> Option<&'r [i32]> {
+        let map = self.named_group_map();
+        let groups = map.get(name).map(|groups| groups.as_slice());
+        // The map stays alive behind the `Arc` cached on `self` for as
+        // long as `self` is, so it's sound to tie the borrow to `'r`
+        // rather than to the local `map` clone.
+        groups.map(|groups| unsafe { &*(groups as *const [i32]) })
+    }
+
+    /// Builds (or returns the cached) map from group name to the indexes
+    /// of the groups it refers to.
+    fn named_group_map(&self) -> Arc<HashMap<String, Vec<i32>>> {
+        let mut cache = self.name_table_cache.lock().unwrap();
+        if let Some(ref map) = *cache {
+            return map.clone();
+        }
+        let mut map = HashMap::with_capacity(self.names_len());
+        self.foreach_name(|name, group_nums| {
+            map.insert(
+                name.to_owned(),
+                group_nums.iter().map(|&g| g as i32).collect()
+            );
+            true
+        });
+        let map = Arc::new(map);
+        *cache = Some(map.clone());
+        map
+    }
+
+    /// Returns the group indexes referred to by the named group `name`
+    /// (given as raw bytes), or `None` if the regex has no group with that
+    /// name.
+    ///
+    /// Use this instead of [`named_group`] when the regex was compiled for
+    /// an encoding where group names aren't guaranteed to be valid UTF-8.
+    ///
+    /// [`named_group`]: #method.named_group
+    pub fn named_group_bytes<'r>(&'r self, name: &[u8]) -> Option<&'r [i32]> {
+        let map = self.named_group_bytes_map();
+        let groups = map.get(name).map(|groups| groups.as_slice());
+        // As with `named_group`, the map is cached on `self` behind an
+        // `Arc`, so it's sound to tie the borrow to `'r`.
+        groups.map(|groups| unsafe { &*(groups as *const [i32]) })
+    }
+
+    /// Builds (or returns the cached) map from raw group name bytes to the
+    /// indexes of the groups it refers to.
+    fn named_group_bytes_map(&self) -> Arc<HashMap<Vec<u8>, Vec<i32>>> {
+        let mut cache = self.name_table_cache_bytes.lock().unwrap();
+        if let Some(ref map) = *cache {
+            return map.clone();
+        }
+        let mut map = HashMap::with_capacity(self.names_len());
+        self.foreach_name_bytes(|name, group_nums| {
+            map.insert(
+                name.to_vec(),
+                group_nums.iter().map(|&g| g as i32).collect()
+            );
+            true
+        });
+        let map = Arc::new(map);
+        *cache = Some(map.clone());
+        map
+    }
+
+    /// Calls `callback` once for each named group in the regex, passing the
+    /// group's name as raw bytes and the indexes of the groups it refers
+    /// to.
+    ///
+    /// Oniguruma can be compiled for encodings (byte/ASCII-compatible,
+    /// Latin-1, EUC, ...) where a group name isn't valid UTF-8, so this is
+    /// the primitive: it never validates the name. [`foreach_name`]
+    /// builds on top of it for the common UTF-8 case.
+    ///
+    /// Iteration stops early if `callback` returns `false`. Returns the
+    /// result of the underlying `onig_foreach_name` call.
+    ///
+    /// [`foreach_name`]: #method.foreach_name
+    pub fn foreach_name_bytes<F>(&self, mut callback: F) -> i32
+        where F: FnMut(&[u8], &[u32]) -> bool
+    {
+        unsafe {
+            onig_sys::onig_foreach_name(
+                self.raw,
+                foreach_name_cb::<F>,
+                &mut callback as *mut F as *mut c_void
+            )
+        }
+    }
+
+    /// Calls `callback` once for each named group in the regex, passing the
+    /// group's name and the indexes of the groups it refers to.
+    ///
+    /// Assumes group names are valid UTF-8, which holds for the UTF-8
+    /// encodings Oniguruma is usually compiled with; use
+    /// [`foreach_name_bytes`] if the regex may have been compiled for an
+    /// encoding where that doesn't hold.
+    ///
+    /// Iteration stops early if `callback` returns `false`. Returns the
+    /// result of the underlying `onig_foreach_name` call.
+    ///
+    /// [`foreach_name_bytes`]: #method.foreach_name_bytes
+    pub fn foreach_name<F>(&self, mut callback: F) -> i32
+        where F: FnMut(&str, &[u32]) -> bool
+    {
+        self.foreach_name_bytes(|name, groups| {
+            callback(unsafe { from_utf8_unchecked(name) }, groups)
+        })
+    }
+
     /// Returns the iterator over named groups as a tuple with the group name
     /// and group indexes.
     pub fn names<'r>(&'r self) -> Names<'r> {
         Names {
-            table: unsafe { (*self.raw).name_table as *const StTable },
-            bin_idx: -1,
-            entry_ptr: null(),
+            names: self.names_table(),
+            pos: 0,
             _phantom: PhantomData
         }
     }
-}
 
-#[repr(C)]
-#[derive(Debug)]
-struct NameEntry {
-    name: *const c_uchar,
-    name_len: c_int,
-    back_num: c_int,
-    back_alloc: c_int,
-    back_ref1: c_int,
-    back_refs: *const c_int
-}
+    /// Returns the iterator over named groups as a tuple with the group
+    /// name (as raw, unvalidated bytes) and group indexes.
+    ///
+    /// Use this instead of [`names`] when the regex was compiled for an
+    /// encoding where group names aren't guaranteed to be valid UTF-8.
+    ///
+    /// [`names`]: #method.names
+    pub fn names_bytes<'r>(&'r self) -> NamesBytes<'r> {
+        NamesBytes {
+            names: self.names_table(),
+            pos: 0,
+            _phantom: PhantomData
+        }
+    }
 
-#[repr(C)]
-#[derive(Debug)]
-struct StTableEntry {
-    hash: c_uint,
-    key: c_ulong,
-    record: c_ulong,
-    next: *const StTableEntry
+    /// Builds (or returns the cached) ordered name table backing both
+    /// [`names`] and [`names_bytes`].
+    ///
+    /// Cached behind an `Arc` on `self.names_table_cache`, same as
+    /// [`named_group_map`], so that `Names`/`NamesBytes` can hand out
+    /// borrows tied to `self`'s lifetime instead of owning (and
+    /// re-walking the name table for) their own copy.
+    ///
+    /// [`names`]: #method.names
+    /// [`names_bytes`]: #method.names_bytes
+    /// [`named_group_map`]: #method.named_group_map
+    fn names_table(&self) -> Arc<Vec<(Box<[u8]>, Box<[i32]>)>> {
+        let mut cache = self.names_table_cache.lock().unwrap();
+        if let Some(ref names) = *cache {
+            return names.clone();
+        }
+        let mut names = Vec::with_capacity(self.names_len());
+        self.foreach_name_bytes(|name, groups| {
+            let groups = groups.iter().map(|&g| g as i32).collect::<Vec<_>>();
+            names.push((name.to_vec().into_boxed_slice(), groups.into_boxed_slice()));
+            true
+        });
+        let names = Arc::new(names);
+        *cache = Some(names.clone());
+        names
+    }
 }
 
-#[repr(C)]
-#[derive(Debug)]
-struct StTable {
-    type_: *const c_void,
-    num_bins: c_int,
-    num_entries: c_int,
-    bins: *const *const StTableEntry
+/// The `extern "C"` trampoline handed to `onig_foreach_name`.
+///
+/// Reconstructs the name and group-index slices from the raw pointers
+/// Oniguruma provides, then forwards them to the `FnMut` stashed in `arg`.
+/// The name is passed through as raw bytes, unvalidated: Oniguruma can be
+/// compiled for encodings where it isn't UTF-8. Returning non-zero here
+/// aborts iteration on the C side, so a `false` from the callback is
+/// reported as `1`.
+extern "C" fn foreach_name_cb<F>(
+    name: *const c_uchar,
+    name_end: *const c_uchar,
+    ngroup_num: c_int,
+    group_nums: *mut c_int,
+    _regex: onig_sys::OnigRegex,
+    arg: *mut c_void
+) -> c_int
+    where F: FnMut(&[u8], &[u32]) -> bool
+{
+    unsafe {
+        let name = from_raw_parts(name, name_end as usize - name as usize);
+        let groups = from_raw_parts(group_nums as *const u32, ngroup_num as usize);
+        let callback = &mut *(arg as *mut F);
+        if callback(name, groups) { 0 } else { 1 }
+    }
 }
 
 /// Names is an iterator over named groups as a tuple with the group name
@@ -62,9 +220,8 @@ struct StTable {
 /// `'r` is the lifetime of the Regex object.
 #[derive(Debug)]
 pub struct Names<'r> {
-    table: *const StTable,
-    bin_idx: c_int,
-    entry_ptr: *const StTableEntry,
+    names: Arc<Vec<(Box<[u8]>, Box<[i32]>)>>,
+    pos: usize,
     _phantom: PhantomData<&'r Regex>
 }
 
@@ -72,25 +229,46 @@ impl<'r> Iterator for Names<'r> {
     type Item = (&'r str, &'r [i32]);
 
     fn next(&mut self) -> Option<(&'r str, &'r [i32])> {
+        let entry = self.names.get(self.pos)?;
+        self.pos += 1;
+        // `self.names` is the same `Arc` cached on the originating
+        // `Regex` (`self.names_table_cache`), so it stays alive for as
+        // long as the `Regex` (and therefore `'r`) does, regardless of
+        // whether this `Names` iterator itself is still alive. Assumes
+        // the name is valid UTF-8; see `names_bytes` otherwise.
         unsafe {
-            while self.entry_ptr.is_null() {
-                if self.table.is_null() || self.bin_idx + 1 >= (*self.table).num_bins {
-                    return None
-                }
-                self.bin_idx += 1;
-                self.entry_ptr = *(*self.table).bins.offset(self.bin_idx as isize)
-            }
-            let entry = (*self.entry_ptr).record as *const NameEntry;
-            let name = from_utf8_unchecked(
-                from_raw_parts((*entry).name, (*entry).name_len as usize)
-            );
-            let groups = if (*entry).back_num > 1 {
-                from_raw_parts((*entry).back_refs, (*entry).back_num as usize)
-            } else {
-                from_raw_parts(&(*entry).back_ref1, 1)
-            };
-            self.entry_ptr = (*self.entry_ptr).next;
-            Some((name, groups))
+            Some((
+                from_utf8_unchecked(&*(entry.0.as_ref() as *const [u8])),
+                &*(entry.1.as_ref() as *const [i32])
+            ))
+        }
+    }
+}
+
+/// NamesBytes is an iterator over named groups as a tuple with the group
+/// name (as raw, unvalidated bytes) and group indexes.
+///
+/// `'r` is the lifetime of the Regex object.
+#[derive(Debug)]
+pub struct NamesBytes<'r> {
+    names: Arc<Vec<(Box<[u8]>, Box<[i32]>)>>,
+    pos: usize,
+    _phantom: PhantomData<&'r Regex>
+}
+
+impl<'r> Iterator for NamesBytes<'r> {
+    type Item = (&'r [u8], &'r [i32]);
+
+    fn next(&mut self) -> Option<(&'r [u8], &'r [i32])> {
+        let entry = self.names.get(self.pos)?;
+        self.pos += 1;
+        // See `Names::next`: `self.names` is the `Arc` cached on the
+        // originating `Regex`, so it outlives this iterator.
+        unsafe {
+            Some((
+                &*(entry.0.as_ref() as *const [u8]),
+                &*(entry.1.as_ref() as *const [i32])
+            ))
         }
     }
 }
@@ -117,6 +295,44 @@ mod tests {
         assert_eq!(names,
                    [("foo", &[1] as &[i32]), ("bar", &[2, 3] as &[i32])]);
     }
-}
 
+    #[test]
+    fn test_regex_named_group() {
+        let regex = Regex::new("(?<foo>he)(?<bar>l+)(?<bar>o)").unwrap();
+        assert_eq!(regex.named_group("foo"), Some(&[1] as &[i32]));
+        assert_eq!(regex.named_group("bar"), Some(&[2, 3] as &[i32]));
+        assert_eq!(regex.named_group("baz"), None);
+        // A second lookup is served from the cached map.
+        assert_eq!(regex.named_group("foo"), Some(&[1] as &[i32]));
+    }
+
+    #[test]
+    fn test_regex_names_bytes() {
+        let regex = Regex::new("(?<foo>he)(?<bar>l+)(?<bar>o)").unwrap();
+        let names = regex.names_bytes().collect::<Vec<_>>();
+        assert_eq!(names,
+                   [(&b"foo"[..], &[1] as &[i32]), (&b"bar"[..], &[2, 3] as &[i32])]);
+    }
 
+    #[test]
+    fn test_regex_named_group_bytes() {
+        let regex = Regex::new("(?<foo>he)(?<bar>l+)(?<bar>o)").unwrap();
+        assert_eq!(regex.named_group_bytes(b"foo"), Some(&[1] as &[i32]));
+        assert_eq!(regex.named_group_bytes(b"bar"), Some(&[2, 3] as &[i32]));
+        assert_eq!(regex.named_group_bytes(b"baz"), None);
+    }
+
+    #[test]
+    fn test_regex_foreach_name() {
+        let regex = Regex::new("(?<foo>he)(?<bar>l+)(?<bar>o)").unwrap();
+        let mut seen = Vec::new();
+        regex.foreach_name(|name, groups| {
+            seen.push((name.to_owned(), groups.to_vec()));
+            true
+        });
+        assert_eq!(seen, vec![
+            ("foo".to_owned(), vec![1]),
+            ("bar".to_owned(), vec![2, 3]),
+        ]);
+    }
+}
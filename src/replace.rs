@@ -0,0 +1,186 @@
+use super::{Captures, Regex};
+
+impl<'t> Captures<'t> {
+    /// Expands `template` into `dst`, replacing group references with the
+    /// text they matched.
+    ///
+    /// Both `${name}`/`${n}` and `\k<name>` refer to a named or numbered
+    /// group; `$$` is a literal `$`. A reference to an unknown name, or to
+    /// a group that didn't participate in the match, expands to the empty
+    /// string. A `${` with no matching `}` (and likewise `\k<` with no
+    /// matching `>`) is copied through literally rather than erroring.
+    pub fn expand(&self, template: &str, dst: &mut String) {
+        let mut rest = template;
+        loop {
+            match rest.find(|c| c == '$' || c == '\\') {
+                None => {
+                    dst.push_str(rest);
+                    return;
+                }
+                Some(idx) => {
+                    dst.push_str(&rest[..idx]);
+                    rest = &rest[idx..];
+                }
+            }
+            if rest.starts_with("$$") {
+                dst.push('$');
+                rest = &rest[2..];
+            } else if rest.starts_with("${") {
+                match rest[2..].find('}') {
+                    Some(end) => {
+                        self.expand_ref(&rest[2..2 + end], dst);
+                        rest = &rest[2 + end + 1..];
+                    }
+                    None => {
+                        dst.push_str("${");
+                        rest = &rest[2..];
+                    }
+                }
+            } else if rest.starts_with("\\k<") {
+                match rest[3..].find('>') {
+                    Some(end) => {
+                        self.expand_ref(&rest[3..3 + end], dst);
+                        rest = &rest[3 + end + 1..];
+                    }
+                    None => {
+                        dst.push_str("\\k<");
+                        rest = &rest[3..];
+                    }
+                }
+            } else {
+                // A lone `$` or `\` that isn't part of a reference we
+                // recognize; copy it through and keep scanning.
+                let ch_len = rest.chars().next().unwrap().len_utf8();
+                dst.push_str(&rest[..ch_len]);
+                rest = &rest[ch_len..];
+            }
+        }
+    }
+
+    /// Looks up `name` as a numbered group first, falling back to the
+    /// regex's named-group table, and appends whatever it matched (or
+    /// nothing, if the name is unknown or the group didn't match).
+    fn expand_ref(&self, name: &str, dst: &mut String) {
+        let text = match name.parse::<usize>() {
+            Ok(i) => self.at(i),
+            Err(_) => {
+                self.regex.named_group(name).and_then(|groups| {
+                    groups.iter().filter_map(|&i| self.at(i as usize)).next()
+                })
+            }
+        };
+        if let Some(text) = text {
+            dst.push_str(text);
+        }
+    }
+}
+
+/// A replacement for a `Regex::replace`/`replace_all` call: either a
+/// template string (expanded via [`Captures::expand`]) or a closure
+/// computing the replacement from the match's captures.
+pub trait Replacer {
+    /// Appends the replacement for `caps` to `dst`.
+    fn replace_append(&mut self, caps: &Captures, dst: &mut String);
+}
+
+impl<'a> Replacer for &'a str {
+    fn replace_append(&mut self, caps: &Captures, dst: &mut String) {
+        caps.expand(*self, dst);
+    }
+}
+
+impl<F> Replacer for F
+    where F: FnMut(&Captures) -> String
+{
+    fn replace_append(&mut self, caps: &Captures, dst: &mut String) {
+        dst.push_str(&(*self)(caps));
+    }
+}
+
+impl Regex {
+    /// Replaces the leftmost-first match in `text` with the output of
+    /// `rep`, returning the resulting string unchanged if there's no
+    /// match.
+    pub fn replace<R: Replacer>(&self, text: &str, rep: R) -> String {
+        self.replacen(text, 1, rep)
+    }
+
+    /// Replaces all non-overlapping matches in `text` with the output of
+    /// `rep`, returning the resulting string unchanged if there's no
+    /// match.
+    pub fn replace_all<R: Replacer>(&self, text: &str, rep: R) -> String {
+        self.replacen(text, 0, rep)
+    }
+
+    /// Replaces at most `limit` non-overlapping matches (or all of them,
+    /// if `limit == 0`) in `text` with the output of `rep`.
+    fn replacen<R: Replacer>(&self, text: &str, limit: usize, mut rep: R) -> String {
+        let mut dst = String::with_capacity(text.len());
+        let mut last_end = 0;
+        for (i, caps) in self.captures_iter(text).enumerate() {
+            if limit > 0 && i >= limit {
+                break;
+            }
+            let (start, end) = caps.pos(0).unwrap();
+            dst.push_str(&text[last_end..start]);
+            rep.replace_append(&caps, &mut dst);
+            last_end = end;
+        }
+        dst.push_str(&text[last_end..]);
+        dst
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    #[test]
+    fn test_expand_named_and_numbered() {
+        let regex = Regex::new("(?<foo>\\w+) (?<bar>\\w+)").unwrap();
+        let caps = regex.captures("hello world").unwrap();
+        let mut dst = String::new();
+        caps.expand("${bar}, ${foo}! (${1}/${2})", &mut dst);
+        assert_eq!(dst, "world, hello! (hello/world)");
+    }
+
+    #[test]
+    fn test_expand_backslash_k() {
+        let regex = Regex::new("(?<foo>\\w+)").unwrap();
+        let caps = regex.captures("hello").unwrap();
+        let mut dst = String::new();
+        caps.expand("<\\k<foo>>", &mut dst);
+        assert_eq!(dst, "<hello>");
+    }
+
+    #[test]
+    fn test_expand_edge_cases() {
+        let regex = Regex::new("(?<foo>\\w+)").unwrap();
+        let caps = regex.captures("hello").unwrap();
+
+        let mut dst = String::new();
+        caps.expand("$$${foo}", &mut dst);
+        assert_eq!(dst, "$hello");
+
+        let mut dst = String::new();
+        caps.expand("${unknown}", &mut dst);
+        assert_eq!(dst, "");
+
+        let mut dst = String::new();
+        caps.expand("${unterminated", &mut dst);
+        assert_eq!(dst, "${unterminated");
+    }
+
+    #[test]
+    fn test_replace_and_replace_all() {
+        let regex = Regex::new("(?<word>\\w+)").unwrap();
+        assert_eq!(regex.replace("hello world", "[${word}]"), "[hello] world");
+        assert_eq!(regex.replace_all("hello world", "[${word}]"), "[hello] [world]");
+        assert_eq!(
+            regex.replace_all("hello world", |caps: &Captures| {
+                caps.at(1).unwrap().to_uppercase()
+            }),
+            "HELLO WORLD"
+        );
+    }
+}